@@ -1,9 +1,446 @@
 use std::collections::HashMap;
+use std::ops::Range;
 
 use colored::*;
-use similar::{ChangeTag, TextDiff};
+use similar::{Algorithm, ChangeTag, TextDiff};
+use unicode_segmentation::UnicodeSegmentation;
 use uuid::Uuid;
 
+// Which unit of text a diff operates over. `Char` is the historical
+// behavior; `Grapheme` diffs over whole grapheme clusters (a combining
+// accent, an emoji with modifiers and ZWJs, ...) so one visible glyph is
+// never split across a color or an offset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DiffUnit {
+    Char,
+    Grapheme,
+}
+
+// Picks which alignment algorithm callers want out of the diff stream.
+// Myers is the general-purpose default; Patience lines up far more
+// intuitively on text with repeated tokens, like mentions or repeated
+// words, because it anchors on tokens that occur exactly once on each side
+// before falling back to Myers on the gaps between them. Lcs computes a
+// plain longest common subsequence, with no unique-token anchoring.
+#[derive(Debug, Clone, Copy)]
+struct DiffConfig {
+    algorithm: Algorithm,
+    unit: DiffUnit,
+    semantic_cleanup: bool,
+}
+
+impl Default for DiffConfig {
+    fn default() -> Self {
+        Self {
+            algorithm: Algorithm::Myers,
+            unit: DiffUnit::Char,
+            semantic_cleanup: false,
+        }
+    }
+}
+
+// Builds a char-level `TextDiff` honoring the configured algorithm, so every
+// call site picks up Patience/Lcs/Myers the same way instead of hardcoding
+// `TextDiff::from_chars`.
+fn diff_chars<'a>(old: &'a str, new: &'a str, config: &DiffConfig) -> TextDiff<'a, 'a, 'a, str> {
+    TextDiff::configure()
+        .algorithm(config.algorithm)
+        .diff_chars(old, new)
+}
+
+// One emitted diff token, unified across the char and grapheme diffing
+// backends: `text` is a single display unit (one char, or one whole
+// grapheme cluster, never split), `old_column`/`new_column` are its visible
+// position counted in those units, and `old_byte_range`/`new_byte_range` are
+// where it actually lives in each source string's bytes. A deletion only has
+// an old-side range, an insertion only a new-side one; an equal run has
+// both, since the same text occupies a (possibly different) byte range on
+// each side.
+#[derive(Debug)]
+struct UnitChange {
+    tag: ChangeTag,
+    text: String,
+    old_column: Option<usize>,
+    new_column: Option<usize>,
+    old_byte_range: Option<Range<usize>>,
+    new_byte_range: Option<Range<usize>>,
+}
+
+// Records one diff token and advances whichever column/byte cursors the
+// tag touches. Shared by both the char and grapheme backends below so the
+// cursor bookkeeping only has to be gotten right once.
+fn record_unit(
+    units: &mut Vec<UnitChange>,
+    tag: ChangeTag,
+    text: String,
+    old_col: &mut usize,
+    new_col: &mut usize,
+    old_byte: &mut usize,
+    new_byte: &mut usize,
+) {
+    let len = text.len();
+    let (old_column, new_column, old_byte_range, new_byte_range) = match tag {
+        ChangeTag::Equal => {
+            let old_range = *old_byte..*old_byte + len;
+            let new_range = *new_byte..*new_byte + len;
+            let columns = (Some(*old_col), Some(*new_col));
+            *old_col += 1;
+            *new_col += 1;
+            *old_byte += len;
+            *new_byte += len;
+            (columns.0, columns.1, Some(old_range), Some(new_range))
+        }
+        ChangeTag::Delete => {
+            let range = *old_byte..*old_byte + len;
+            let column = Some(*old_col);
+            *old_col += 1;
+            *old_byte += len;
+            (column, None, Some(range), None)
+        }
+        ChangeTag::Insert => {
+            let range = *new_byte..*new_byte + len;
+            let column = Some(*new_col);
+            *new_col += 1;
+            *new_byte += len;
+            (None, column, None, Some(range))
+        }
+    };
+    units.push(UnitChange {
+        tag,
+        text,
+        old_column,
+        new_column,
+        old_byte_range,
+        new_byte_range,
+    });
+}
+
+// Diffs `old` and `new` as a stream of `UnitChange`s, tokenizing by char or
+// by grapheme cluster depending on `config.unit`. This is what keeps a
+// multi-codepoint grapheme (emoji with modifiers, combining marks) from
+// being split across two different-colored `ChangeDetail`s, and gives every
+// token an exact visible-column position instead of its raw byte value.
+fn diff_units(old: &str, new: &str, config: &DiffConfig) -> Vec<UnitChange> {
+    let mut units = Vec::new();
+    let mut old_col = 0usize;
+    let mut new_col = 0usize;
+    let mut old_byte = 0usize;
+    let mut new_byte = 0usize;
+
+    match config.unit {
+        DiffUnit::Char => {
+            for change in diff_chars(old, new, config).iter_all_changes() {
+                record_unit(
+                    &mut units,
+                    change.tag(),
+                    change.value().to_string(),
+                    &mut old_col,
+                    &mut new_col,
+                    &mut old_byte,
+                    &mut new_byte,
+                );
+            }
+        }
+        DiffUnit::Grapheme => {
+            let old_graphemes: Vec<&str> = old.graphemes(true).collect();
+            let new_graphemes: Vec<&str> = new.graphemes(true).collect();
+            let diff = TextDiff::configure()
+                .algorithm(config.algorithm)
+                .diff_slices(&old_graphemes, &new_graphemes);
+            for change in diff.iter_all_changes() {
+                record_unit(
+                    &mut units,
+                    change.tag(),
+                    change.value().to_string(),
+                    &mut old_col,
+                    &mut new_col,
+                    &mut old_byte,
+                    &mut new_byte,
+                );
+            }
+        }
+    }
+
+    units
+}
+
+// Turns a unit-change stream into the `ChangeDetail`s used for rendering
+// and stats. Equal tokens are colored by whether they kept their column
+// ("Same Index") or shifted ("Different Index") between old and new.
+fn units_to_change_details(units: &[UnitChange]) -> Vec<ChangeDetail> {
+    units
+        .iter()
+        .map(|unit| match unit.tag {
+            ChangeTag::Equal => {
+                let change_type = if unit.old_column == unit.new_column {
+                    CharChangeType::EqualIndex
+                } else {
+                    CharChangeType::EqualDifferentIndex
+                };
+                ChangeDetail {
+                    index: unit.new_column.unwrap(),
+                    value: unit.text.clone(),
+                    color: color_equal(&change_type),
+                    bg_color: bg_color_equal(&change_type),
+                    change_type,
+                }
+            }
+            ChangeTag::Delete => ChangeDetail {
+                index: unit.old_column.unwrap(),
+                value: unit.text.clone(),
+                change_type: CharChangeType::Deletion,
+                color: delete_fg(),
+                bg_color: delete_bg(),
+            },
+            ChangeTag::Insert => ChangeDetail {
+                index: unit.new_column.unwrap(),
+                value: unit.text.clone(),
+                change_type: CharChangeType::Insertion,
+                color: insert_fg(),
+                bg_color: insert_bg(),
+            },
+        })
+        .collect()
+}
+
+// One replace region within a diff, anchored to `old` by byte offset: the
+// unchanged text immediately preceding the edit (`context`), the exact old
+// text it replaces (`deletion`), and the new text that takes its place
+// (`insertion`). Keeping `context` around lets `apply_patch` detect when
+// the base text it's being applied to has drifted from the one the patch
+// was computed against, instead of silently corrupting it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PatchHunk {
+    old_offset: usize,
+    context: String,
+    deletion: String,
+    insertion: String,
+}
+
+// Structured failure from `apply_patch`: which hunk failed, and what
+// context/deletion text it expected to find in the base versus what was
+// actually there, so callers can decide how to reconcile a drifted buffer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PatchError {
+    ContextMismatch {
+        hunk_index: usize,
+        expected: String,
+        found: String,
+    },
+    MalformedPatch,
+}
+
+impl std::fmt::Display for PatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PatchError::ContextMismatch {
+                hunk_index,
+                expected,
+                found,
+            } => write!(
+                f,
+                "hunk {hunk_index} expected '{expected}' but found '{found}' in base text"
+            ),
+            PatchError::MalformedPatch => write!(f, "malformed patch text"),
+        }
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+// Builds a portable patch from the char-level diff between `old` and
+// `new`: every replace region becomes one `PatchHunk`, carrying its
+// leading context so it can be validated, at its exact `old_offset`,
+// against a (possibly drifted) copy of `old` elsewhere.
+fn make_patch(old: &str, new: &str) -> Vec<PatchHunk> {
+    let config = DiffConfig::default();
+    let diff = diff_chars(old, new, &config);
+
+    let mut hunks = Vec::new();
+    let mut old_byte = 0usize;
+    let mut context_start = 0usize;
+    let mut context = String::new();
+    let mut deletion = String::new();
+    let mut insertion = String::new();
+    let mut in_edit = false;
+
+    for change in diff.iter_all_changes() {
+        let text = change.value();
+        match change.tag() {
+            ChangeTag::Equal => {
+                if in_edit {
+                    hunks.push(PatchHunk {
+                        old_offset: context_start,
+                        context: std::mem::take(&mut context),
+                        deletion: std::mem::take(&mut deletion),
+                        insertion: std::mem::take(&mut insertion),
+                    });
+                    in_edit = false;
+                    context_start = old_byte;
+                }
+                context.push_str(text);
+                old_byte += text.len();
+            }
+            ChangeTag::Delete => {
+                in_edit = true;
+                deletion.push_str(text);
+                old_byte += text.len();
+            }
+            ChangeTag::Insert => {
+                in_edit = true;
+                insertion.push_str(text);
+            }
+        }
+    }
+    if in_edit {
+        hunks.push(PatchHunk {
+            old_offset: context_start,
+            context,
+            deletion,
+            insertion,
+        });
+    }
+
+    hunks
+}
+
+// Replays `hunks` against `old`, validating each hunk's context and
+// deletion text against the base before touching it. Returns a structured
+// `PatchError::ContextMismatch` naming the first hunk whose expected text
+// no longer matches, so last-writer-style merges can surface exactly where
+// the buffer drifted instead of applying a corrupted patch.
+fn apply_patch(old: &str, hunks: &[PatchHunk]) -> Result<String, PatchError> {
+    let mut result = String::with_capacity(old.len());
+    let mut cursor = 0usize;
+
+    for (hunk_index, hunk) in hunks.iter().enumerate() {
+        if hunk.old_offset < cursor || hunk.old_offset > old.len() {
+            return Err(PatchError::ContextMismatch {
+                hunk_index,
+                expected: format!("{}{}", hunk.context, hunk.deletion),
+                found: String::new(),
+            });
+        }
+        // `hunk.old_offset` comes from a parsed/possibly-foreign patch and
+        // may not land on a char boundary; `get` turns that into the same
+        // structured error instead of an indexing panic.
+        let prefix = old.get(cursor..hunk.old_offset).ok_or_else(|| PatchError::ContextMismatch {
+            hunk_index,
+            expected: format!("{}{}", hunk.context, hunk.deletion),
+            found: String::new(),
+        })?;
+        result.push_str(prefix);
+
+        let context_end = hunk.old_offset + hunk.context.len();
+        let deletion_end = context_end + hunk.deletion.len();
+        let expected = format!("{}{}", hunk.context, hunk.deletion);
+        let found = old.get(hunk.old_offset..deletion_end.min(old.len()));
+
+        if found != Some(expected.as_str()) {
+            return Err(PatchError::ContextMismatch {
+                hunk_index,
+                expected,
+                found: found.unwrap_or("").to_string(),
+            });
+        }
+
+        result.push_str(&hunk.context);
+        result.push_str(&hunk.insertion);
+        cursor = deletion_end;
+    }
+
+    result.push_str(&old[cursor..]);
+    Ok(result)
+}
+
+// Escapes newlines and backslashes so a hunk field always fits on one line
+// of the serialized patch text.
+fn escape_patch_field(field: &str) -> String {
+    field.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape_patch_field(field: &str) -> String {
+    let mut out = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('\\') => out.push('\\'),
+                Some(other) => {
+                    out.push('\\');
+                    out.push(other);
+                }
+                None => out.push('\\'),
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+// Compact text serialization of a patch: one `@@` header per hunk giving
+// its old-text offset and context length, followed by the context (`=`),
+// deletion (`-`) and insertion (`+`) lines. Designed to be shipped over the
+// wire and replayed with `parse_patch` + `apply_patch` on the other side.
+fn serialize_patch(hunks: &[PatchHunk]) -> String {
+    let mut out = String::new();
+    for hunk in hunks {
+        out.push_str(&format!(
+            "@@ offset={} context_len={} @@\n",
+            hunk.old_offset,
+            hunk.context.len()
+        ));
+        out.push_str(&format!("={}\n", escape_patch_field(&hunk.context)));
+        out.push_str(&format!("-{}\n", escape_patch_field(&hunk.deletion)));
+        out.push_str(&format!("+{}\n", escape_patch_field(&hunk.insertion)));
+    }
+    out
+}
+
+fn parse_patch(text: &str) -> Result<Vec<PatchHunk>, PatchError> {
+    let mut hunks = Vec::new();
+    let mut lines = text.lines();
+
+    while let Some(header) = lines.next() {
+        let old_offset: usize = header
+            .split_whitespace()
+            .find_map(|field| field.strip_prefix("offset="))
+            .and_then(|value| value.parse().ok())
+            .ok_or(PatchError::MalformedPatch)?;
+        let context_len: usize = header
+            .split_whitespace()
+            .find_map(|field| field.strip_prefix("context_len="))
+            .and_then(|value| value.parse().ok())
+            .ok_or(PatchError::MalformedPatch)?;
+
+        let context_line = lines.next().ok_or(PatchError::MalformedPatch)?;
+        let deletion_line = lines.next().ok_or(PatchError::MalformedPatch)?;
+        let insertion_line = lines.next().ok_or(PatchError::MalformedPatch)?;
+
+        let context = unescape_patch_field(
+            context_line.strip_prefix('=').ok_or(PatchError::MalformedPatch)?,
+        );
+        if context.len() != context_len {
+            return Err(PatchError::MalformedPatch);
+        }
+
+        hunks.push(PatchHunk {
+            old_offset,
+            context,
+            deletion: unescape_patch_field(
+                deletion_line.strip_prefix('-').ok_or(PatchError::MalformedPatch)?,
+            ),
+            insertion: unescape_patch_field(
+                insertion_line.strip_prefix('+').ok_or(PatchError::MalformedPatch)?,
+            ),
+        });
+    }
+
+    Ok(hunks)
+}
+
 fn main() {
     // Initialize people and UUID mappings
     let mut people: HashMap<Uuid, &str> = HashMap::new();
@@ -15,82 +452,65 @@ fn main() {
     let new: String = "hi, #John".to_string();
 
     // Build the current highlight index
-    let current_highlight_index = build_highlight_index(&old, id, value);
+    let current_highlight_index = build_highlight_index(&old, &[(id, value)]);
+
+    // Pick the alignment algorithm and tokenization unit once and thread
+    // them through the mention diffs below (`make_patch` and the line-level
+    // demo diff with `DiffConfig::default()`)
+    let diff_config = DiffConfig {
+        semantic_cleanup: true,
+        ..DiffConfig::default()
+    };
 
-    // Create a TextDiff object using the Myers algorithm
-    let diff = TextDiff::from_chars(&old, &new);
+    // Diff as a stream of unified tokens (one char or one grapheme cluster
+    // each, per `diff_config.unit`), with exact visible-column positions
+    let units = diff_units(&old, &new, &diff_config);
 
     // Prepare to track changes
-    let mut change_details = Vec::new();
+    let mut change_details = units_to_change_details(&units);
     let mut char_diff_result = CharDiffResult {
         insertions: Vec::new(),
         deletions: Vec::new(),
         equal_matches: Vec::new(),
     };
 
-    // Process changes from diff only once
-    let mut equal_changes = Vec::new();
-    for (idx, change) in diff.iter_all_changes().enumerate() {
-        let value = change.value().to_string();
-        match change.tag() {
+    // Process changes from the unit stream only once
+    for unit in &units {
+        match unit.tag {
             ChangeTag::Equal => {
-                let change_type = if idx == change.value().as_bytes()[0] as usize {
-                    CharChangeType::EqualIndex
-                } else {
-                    CharChangeType::EqualDifferentIndex
-                };
-
-                change_details.push(ChangeDetail {
-                    index: idx,
-                    value: value.clone(),
-                    color: color_equal(&change_type),
-                    bg_color: bg_color_equal(&change_type),
-                    change_type: change_type,
+                char_diff_result.equal_matches.push(EqualCharPair {
+                    old_info: CharMetadata {
+                        index: unit.old_column.unwrap(),
+                        char: unit.text.clone(),
+                        byte_range: unit.old_byte_range.clone().unwrap(),
+                    },
+                    new_info: CharMetadata {
+                        index: unit.new_column.unwrap(),
+                        char: unit.text.clone(),
+                        byte_range: unit.new_byte_range.clone().unwrap(),
+                    },
                 });
-
-                equal_changes.push((idx, value.clone()));
             }
             ChangeTag::Delete => {
                 char_diff_result.deletions.push(CharMetadata {
-                    index: idx,
-                    char: value.clone(),
-                });
-                change_details.push(ChangeDetail {
-                    index: idx,
-                    value,
-                    change_type: CharChangeType::Deletion,
-                    color: delete_fg(),
-                    bg_color: delete_bg(),
+                    index: unit.old_column.unwrap(),
+                    char: unit.text.clone(),
+                    byte_range: unit.old_byte_range.clone().unwrap(),
                 });
             }
             ChangeTag::Insert => {
                 char_diff_result.insertions.push(CharMetadata {
-                    index: idx,
-                    char: value.clone(),
-                });
-                change_details.push(ChangeDetail {
-                    index: idx,
-                    value,
-                    change_type: CharChangeType::Insertion,
-                    color: insert_fg(),
-                    bg_color: insert_bg(),
+                    index: unit.new_column.unwrap(),
+                    char: unit.text.clone(),
+                    byte_range: unit.new_byte_range.clone().unwrap(),
                 });
             }
         }
     }
 
-    // Process equal matches
-    for (old_idx, (new_idx, char)) in equal_changes.iter().enumerate() {
-        char_diff_result.equal_matches.push(EqualCharPair {
-            old_info: CharMetadata {
-                index: old_idx,
-                char: char.clone(),
-            },
-            new_info: CharMetadata {
-                index: *new_idx,
-                char: char.clone(),
-            },
-        });
+    // Coalesce noisy single-token runs into human-meaningful change blocks
+    if diff_config.semantic_cleanup {
+        change_details = cleanup_semantic(change_details);
     }
 
     // Print results
@@ -99,7 +519,8 @@ fn main() {
     print_equal_char_ranges(&char_diff_result);
 
     // Update and display highlight indexes
-    let updated_highlight_index = update_highlight_index(&old, &new, &current_highlight_index);
+    let updated_highlight_index =
+        update_highlight_index(&old, &new, &current_highlight_index, &diff_config);
     println!("Current Highlight Index: {:?}", current_highlight_index);
     println!("Updated Highlight Index: {:?}", updated_highlight_index);
 
@@ -110,6 +531,88 @@ fn main() {
     println!("Unchanged Parts: {}", stats.unchanged);
     println!("Insertions: {}", stats.insertions);
     println!("Deletions: {}", stats.deletions);
+
+    // Demonstrate the grapheme-aware mode on a non-ASCII mention, where a
+    // char-level diff would risk splitting a multi-codepoint cluster (an
+    // emoji with a ZWJ sequence here) across two differently colored runs
+    let grapheme_old = "hi #José 👨‍👩‍👧‍👦 team";
+    let grapheme_new = "hi #Jose 👨‍👩‍👧‍👦 crew";
+    let grapheme_config = DiffConfig {
+        unit: DiffUnit::Grapheme,
+        ..diff_config
+    };
+    println!("\n🧬 Grapheme-Aware Diff:");
+    for unit in diff_units(grapheme_old, grapheme_new, &grapheme_config) {
+        println!(
+            "{:?} '{}' (old col: {:?}, new col: {:?}, old bytes: {:?}, new bytes: {:?})",
+            unit.tag, unit.text, unit.old_column, unit.new_column, unit.old_byte_range, unit.new_byte_range
+        );
+    }
+
+    // Demonstrate semantic cleanup on a naturally noisy char diff: swapping
+    // every other letter produces an Equal/Delete/Insert token for every
+    // single character, with no block longer than one char anywhere
+    let noisy_old = "ABCDEFG";
+    let noisy_new = "AXCYEZG";
+    let noisy_units = diff_units(noisy_old, noisy_new, &diff_config);
+    let raw_details = units_to_change_details(&noisy_units);
+    let cleaned_details = cleanup_semantic(units_to_change_details(&noisy_units));
+    println!("\n🧹 Semantic Cleanup (before -> after):");
+    println!("Before: {} blocks", raw_details.len());
+    println!("After: {} blocks", cleaned_details.len());
+    for detail in &cleaned_details {
+        println!("{:?} '{}'", detail.change_type, detail.value.escape_debug());
+    }
+
+    // Demonstrate shipping a patch computed here and replaying it elsewhere
+    let patch = make_patch(&old, &new);
+    let serialized = serialize_patch(&patch);
+    println!("\n📦 Portable Patch:");
+    print!("{serialized}");
+    let round_tripped = parse_patch(&serialized).expect("patch we just serialized must parse");
+    match apply_patch(&old, &round_tripped) {
+        Ok(applied) => println!("Applied patch reproduces new text: {}", applied == new),
+        Err(err) => println!("Failed to apply patch: {err}"),
+    }
+
+    // A patch applied against drifted base text should fail loudly instead
+    // of silently corrupting it
+    let drifted_old = old.replacen('J', "j", 1);
+    match apply_patch(&drifted_old, &patch) {
+        Ok(_) => println!("Unexpectedly applied cleanly against drifted base text"),
+        Err(err) => println!("Drifted base correctly rejected: {err}"),
+    }
+
+    // Demonstrate multi-entity highlighting where one mention is a literal
+    // prefix of another, the overlap case a single-entity index couldn't handle
+    let overlap_text = "#JohnDoe and #John are both here";
+    let second_id = Uuid::new_v4();
+    people.insert(second_id, "#John");
+    let entities = vec![(id, value), (second_id, "#John")];
+    let overlap_index = build_highlight_index(overlap_text, &entities);
+    println!("\n🏷️  Multi-Entity Highlight Index: {overlap_index:?}");
+    print_highlighted_text(overlap_text, &overlap_index);
+
+    // Demonstrate the line-oriented unified/side-by-side renderers on a
+    // multi-line diff, reusing the same ChangeDetail stream the char-level
+    // demo above already knows how to produce
+    let old_doc = "intro\nfirst line\nsecond line\ntrailing";
+    let new_doc = "intro\nFIRST line\nsecond line\nnew line\ntrailing";
+    let doc_units = diff_units(old_doc, new_doc, &DiffConfig::default());
+    let doc_details = units_to_change_details(&doc_units);
+    let rows = group_into_rows(&doc_details);
+    let theme = Theme::default();
+
+    let mut unified = String::new();
+    render_unified(&rows, Some(&theme), &mut unified).expect("writing to a String cannot fail");
+    println!("\n📜 Unified Diff:");
+    print!("{unified}");
+
+    let mut side_by_side = String::new();
+    render_side_by_side(&rows, Some(&theme), &mut side_by_side)
+        .expect("writing to a String cannot fail");
+    println!("\n📑 Side-by-Side Diff:");
+    print!("{side_by_side}");
 }
 
 // enum for change_type
@@ -121,77 +624,378 @@ enum CharChangeType {
     Deletion,
 }
 
-// Builds the highlight index for initial text
-fn build_highlight_index(old: &str, id: Uuid, value: &str) -> HashMap<Uuid, Vec<usize>> {
+// Builds the highlight index for initial text across any number of labeled
+// entities. Every occurrence of every label is collected as a candidate
+// span first; then candidates are resolved into disjoint per-UUID byte
+// ranges so two mentions can never corrupt each other's index, even when
+// one label is a literal substring of another (e.g. "#John" inside
+// "#JohnDoe").
+//
+// Tie-break policy, applied in order: longest match wins; among equal
+// lengths, the earlier starting offset wins; among ties on both, the
+// smaller UUID wins (an arbitrary but fully deterministic last resort).
+fn build_highlight_index(old: &str, entities: &[(Uuid, &str)]) -> HashMap<Uuid, Vec<usize>> {
+    let mut candidates: Vec<(Uuid, Range<usize>)> = Vec::new();
+    for &(id, value) in entities {
+        if value.is_empty() {
+            continue;
+        }
+        let mut start = 0;
+        while let Some(pos) = old[start..].find(value) {
+            let actual_pos = start + pos;
+            candidates.push((id, actual_pos..actual_pos + value.len()));
+            // Advance to the next char boundary, not just the next byte: a
+            // multi-byte first char would otherwise land `start` mid-codepoint
+            // and panic the next `old[start..]` slice.
+            start = actual_pos + old[actual_pos..].chars().next().map_or(1, char::len_utf8);
+        }
+    }
+
+    candidates.sort_by(|(id_a, range_a), (id_b, range_b)| {
+        range_b
+            .len()
+            .cmp(&range_a.len())
+            .then(range_a.start.cmp(&range_b.start))
+            .then(id_a.cmp(id_b))
+    });
+
+    let mut accepted: Vec<Range<usize>> = Vec::new();
     let mut highlight_index: HashMap<Uuid, Vec<usize>> = HashMap::new();
-    let mut start = 0;
-    while let Some(pos) = old[start..].find(value) {
-        let actual_pos = start + pos;
-        highlight_index
-            .entry(id)
-            .or_default()
-            .extend(actual_pos..actual_pos + value.len());
-        start = actual_pos + 1;
+    for (id, range) in candidates {
+        let overlaps_accepted = accepted
+            .iter()
+            .any(|existing| existing.start < range.end && range.start < existing.end);
+        if overlaps_accepted {
+            continue;
+        }
+        highlight_index.entry(id).or_default().extend(range.clone());
+        accepted.push(range);
     }
+
     highlight_index
 }
 
+// Derives a stable, visually distinct color for a UUID by hashing its
+// bytes (FNV-1a), so every highlighted entity keeps the same color across
+// renders without a manually maintained palette, and abutting spans from
+// different UUIDs are still told apart.
+fn color_for_uuid(id: &Uuid) -> CustomColor {
+    let mut hash: u32 = 0x811c9dc5;
+    for &byte in id.as_bytes() {
+        hash ^= byte as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+    let r = 80 + ((hash & 0xFF) as u8 % 160);
+    let g = 80 + (((hash >> 8) & 0xFF) as u8 % 160);
+    let b = 80 + (((hash >> 16) & 0xFF) as u8 % 160);
+    CustomColor::new(r, g, b)
+}
+
+// Renders `text` with each labeled entity colored by its UUID-derived
+// color, so many distinct highlighted entities (including ones whose spans
+// directly abut) can be shown at once without a manual color assignment.
+fn print_highlighted_text(text: &str, highlight_index: &HashMap<Uuid, Vec<usize>>) {
+    let mut owner: HashMap<usize, Uuid> = HashMap::new();
+    for (id, offsets) in highlight_index {
+        for &offset in offsets {
+            owner.insert(offset, *id);
+        }
+    }
+
+    println!("\n🏷️  Highlighted Entities:");
+    for (byte_offset, ch) in text.char_indices() {
+        match owner.get(&byte_offset) {
+            Some(id) => {
+                let color = color_for_uuid(id);
+                print!("{}", ch.to_string().truecolor(color.r, color.g, color.b).bold());
+            }
+            None => print!("{ch}"),
+        }
+    }
+    println!();
+}
+
+// Builds an exact old-byte -> new-byte offset map from the diff's change
+// stream. Byte offsets, not char offsets, because that's what the highlight
+// index (`build_highlight_index`) and every other caller of `remap_offsets`
+// actually track; char positions only coincide with byte positions for
+// all-ASCII text.
+//
+// Goes through `diff_units` rather than calling `diff_chars` directly, so
+// `config.unit` is honored: a `DiffUnit::Grapheme` config diffs by grapheme
+// cluster, keeping a multi-codepoint glyph that straddles an edit from
+// throwing off the byte alignment for every offset after it. Every byte in
+// an `Equal` unit's old-side range maps 1:1 to the same position in its
+// new-side range (the two are the same text, and so the same length);
+// `Delete`/`Insert` units have no counterpart on the other side and
+// contribute nothing to the map.
+fn build_offset_map(old: &str, new: &str, config: &DiffConfig) -> HashMap<usize, usize> {
+    let mut offset_map = HashMap::new();
+
+    for unit in diff_units(old, new, config) {
+        if unit.tag != ChangeTag::Equal {
+            continue;
+        }
+        let old_range = unit.old_byte_range.unwrap();
+        let new_range = unit.new_byte_range.unwrap();
+        for (old_byte, new_byte) in old_range.zip(new_range) {
+            offset_map.insert(old_byte, new_byte);
+        }
+    }
+
+    offset_map
+}
+
+// Remaps a set of old-string byte offsets onto `new` using an exact diff
+// alignment, rather than re-scanning `new` and hoping the scan doesn't get
+// interrupted. Offsets that fell inside a deleted run have no image in `new`
+// and come back as `None`; everything else comes back as `Some(new_offset)`.
+//
+// This is intentionally generic over "any span of offsets a caller wants to
+// track" (highlights today, but anchors/cursors/selections tomorrow), not
+// just the single highlighted mention `update_highlight_index` used to
+// assume.
+fn remap_offsets(
+    old: &str,
+    new: &str,
+    offsets: &[usize],
+    config: &DiffConfig,
+) -> Vec<Option<usize>> {
+    let offset_map = build_offset_map(old, new, config);
+    offsets.iter().map(|idx| offset_map.get(idx).copied()).collect()
+}
+
 fn update_highlight_index(
     old: &str,
     new: &str,
     current_highlight_index: &HashMap<Uuid, Vec<usize>>,
+    config: &DiffConfig,
 ) -> HashMap<Uuid, Vec<usize>> {
     let mut updated_highlight_index = HashMap::new();
 
-    // Create a TextDiff object to track changes
-    let diff = TextDiff::from_chars(old, new);
-
     for (uuid, indexes) in current_highlight_index {
-        let mut new_indexes = Vec::new();
-
-        // Convert the indexes to the characters they represent
-        let original_chars: Vec<char> = indexes
-            .iter()
-            .map(|&idx| old.chars().nth(idx).unwrap())
+        // Drop any offset that landed inside a deleted run; keep the rest in
+        // their original relative order so a partially-clipped highlight
+        // still maps to the surviving part of the span.
+        let new_indexes: Vec<usize> = remap_offsets(old, new, indexes, config)
+            .into_iter()
+            .flatten()
             .collect();
 
-        // Track the first index after any initial changes
-        let mut first_match_index = None;
+        if !new_indexes.is_empty() {
+            updated_highlight_index.insert(*uuid, new_indexes);
+        }
+    }
+
+    updated_highlight_index
+}
+
+// Which side of a replace a coalesced run of `ChangeDetail`s belongs to.
+// `Equal` covers both `CharChangeType::EqualIndex` and `EqualDifferentIndex`
+// for the purposes of grouping and cleanup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BlockClass {
+    Equal,
+    Insert,
+    Delete,
+}
+
+// A contiguous run of same-class `ChangeDetail`s, kept down to just the
+// (index, token text) pairs cleanup actually needs to move around. For an
+// `Equal` block, `same_index` records whether its tokens kept the
+// `EqualIndex` vs `EqualDifferentIndex` distinction — constant across the
+// whole run, since old/new columns advance in lockstep within one equal run.
+struct ChangeBlock {
+    class: BlockClass,
+    entries: Vec<(usize, String)>,
+    same_index: bool,
+}
+
+fn block_class(change_type: &CharChangeType) -> BlockClass {
+    match change_type {
+        CharChangeType::EqualIndex | CharChangeType::EqualDifferentIndex => BlockClass::Equal,
+        CharChangeType::Insertion => BlockClass::Insert,
+        CharChangeType::Deletion => BlockClass::Delete,
+    }
+}
+
+// Groups a flat, per-token change stream into contiguous same-class blocks
+// so `cleanup_semantic` can reason about "this equality" vs "this edit"
+// instead of individual characters.
+fn coalesce_blocks(changes: Vec<ChangeDetail>) -> Vec<ChangeBlock> {
+    let mut blocks: Vec<ChangeBlock> = Vec::new();
+    for detail in changes {
+        let class = block_class(&detail.change_type);
+        let same_index = matches!(detail.change_type, CharChangeType::EqualIndex);
+        match blocks.last_mut() {
+            Some(last) if last.class == class => last.entries.push((detail.index, detail.value)),
+            _ => blocks.push(ChangeBlock {
+                class,
+                entries: vec![(detail.index, detail.value)],
+                same_index,
+            }),
+        }
+    }
+    blocks
+}
+
+// Sums the token count of whichever `class` of block appears contiguously
+// on one side of `idx`, stopping at the next equality (or the array edge).
+// This is the "length of the nearest equalities/edits on each side" the
+// cleanup decision is based on.
+fn adjacent_len(blocks: &[ChangeBlock], idx: usize, step: isize, class: BlockClass) -> usize {
+    let mut i = idx as isize + step;
+    let mut total = 0;
+    while i >= 0 && (i as usize) < blocks.len() {
+        let block = &blocks[i as usize];
+        if block.class == BlockClass::Equal {
+            break;
+        }
+        if block.class == class {
+            total += block.entries.len();
+        }
+        i += step;
+    }
+    total
+}
 
-        // Iterate through new string to find matching characters
-        for (new_idx, change) in diff.iter_all_changes().enumerate() {
-            if change.tag() == ChangeTag::Equal {
-                let change_char = change.value().chars().next().unwrap();
+// Rebuilds the block list with the marked equalities removed, filing their
+// tokens as both a deletion and an insertion (they leave the old side and
+// land, unchanged, on the new side) and re-merging the now-adjacent
+// insert/delete runs into single contiguous operations, deletions before
+// insertions per the usual replace convention.
+fn rebuild_without_equalities(blocks: Vec<ChangeBlock>, remove: &[bool]) -> Vec<ChangeBlock> {
+    let mut result = Vec::new();
+    let mut pending_delete: Vec<(usize, String)> = Vec::new();
+    let mut pending_insert: Vec<(usize, String)> = Vec::new();
 
-                // If this is the first char of our tracked sequence
-                if first_match_index.is_none() && original_chars.first() == Some(&change_char) {
-                    first_match_index = Some(new_idx);
+    for (i, block) in blocks.into_iter().enumerate() {
+        match block.class {
+            BlockClass::Equal if remove[i] => {
+                pending_delete.extend(block.entries.iter().cloned());
+                pending_insert.extend(block.entries);
+            }
+            BlockClass::Equal => {
+                if !pending_delete.is_empty() {
+                    result.push(ChangeBlock {
+                        class: BlockClass::Delete,
+                        entries: std::mem::take(&mut pending_delete),
+                        same_index: false,
+                    });
+                }
+                if !pending_insert.is_empty() {
+                    result.push(ChangeBlock {
+                        class: BlockClass::Insert,
+                        entries: std::mem::take(&mut pending_insert),
+                        same_index: false,
+                    });
                 }
+                result.push(block);
+            }
+            BlockClass::Delete => pending_delete.extend(block.entries),
+            BlockClass::Insert => pending_insert.extend(block.entries),
+        }
+    }
+    if !pending_delete.is_empty() {
+        result.push(ChangeBlock {
+            class: BlockClass::Delete,
+            entries: pending_delete,
+            same_index: false,
+        });
+    }
+    if !pending_insert.is_empty() {
+        result.push(ChangeBlock {
+            class: BlockClass::Insert,
+            entries: pending_insert,
+            same_index: false,
+        });
+    }
+    result
+}
 
-                // If we have a first match, continue tracking the sequence
-                if let Some(_) = first_match_index {
-                    if new_indexes.is_empty() || new_idx == new_indexes.last().unwrap() + 1 {
-                        if original_chars.get(new_indexes.len()) == Some(&change_char) {
-                            new_indexes.push(new_idx);
-                        } else if !new_indexes.is_empty() {
-                            // Sequence interrupted
-                            break;
-                        }
+// Expands coalesced blocks back into one `ChangeDetail` per block, so a
+// merged run of edits reads (and counts, in `calculate_stats`) as a single
+// human-meaningful change instead of N single-token ones.
+fn flatten_blocks(blocks: Vec<ChangeBlock>) -> Vec<ChangeDetail> {
+    blocks
+        .into_iter()
+        .filter(|block| !block.entries.is_empty())
+        .map(|block| {
+            let index = block.entries[0].0;
+            let value: String = block.entries.iter().map(|(_, text)| text.as_str()).collect();
+            match block.class {
+                BlockClass::Insert => ChangeDetail {
+                    index,
+                    value,
+                    change_type: CharChangeType::Insertion,
+                    color: insert_fg(),
+                    bg_color: insert_bg(),
+                },
+                BlockClass::Delete => ChangeDetail {
+                    index,
+                    value,
+                    change_type: CharChangeType::Deletion,
+                    color: delete_fg(),
+                    bg_color: delete_bg(),
+                },
+                BlockClass::Equal => {
+                    let change_type = if block.same_index {
+                        CharChangeType::EqualIndex
                     } else {
-                        // Non-consecutive index
-                        break;
+                        CharChangeType::EqualDifferentIndex
+                    };
+                    ChangeDetail {
+                        index,
+                        value,
+                        color: color_equal(&change_type),
+                        bg_color: bg_color_equal(&change_type),
+                        change_type,
                     }
                 }
             }
+        })
+        .collect()
+}
+
+// Semantic cleanup pass (modeled on diff_match_patch's cleanupSemantic):
+// coalesces the raw per-token change stream into blocks, then repeatedly
+// looks for an equality block no longer than the larger of the insertion
+// and deletion totals bordering it, on each side — a spurious "tiny equality" sitting
+// inside what's really one big replace — and folds it into the surrounding
+// edit. Iterates to a fixed point, since removing one equality can expose a
+// new, now-larger edit region bordering the next one.
+fn cleanup_semantic(changes: Vec<ChangeDetail>) -> Vec<ChangeDetail> {
+    let mut blocks = coalesce_blocks(changes);
+
+    loop {
+        let mut remove = vec![false; blocks.len()];
+        let mut changed = false;
+
+        for (i, block) in blocks.iter().enumerate() {
+            if block.class != BlockClass::Equal {
+                continue;
+            }
+            let equality_len = block.entries.len();
+            let before_insert = adjacent_len(&blocks, i, -1, BlockClass::Insert);
+            let before_delete = adjacent_len(&blocks, i, -1, BlockClass::Delete);
+            let after_insert = adjacent_len(&blocks, i, 1, BlockClass::Insert);
+            let after_delete = adjacent_len(&blocks, i, 1, BlockClass::Delete);
+
+            let bordered_by_edits = before_insert + before_delete + after_insert + after_delete > 0;
+            if bordered_by_edits
+                && equality_len <= before_insert.max(before_delete)
+                && equality_len <= after_insert.max(after_delete)
+            {
+                remove[i] = true;
+                changed = true;
+            }
         }
 
-        // Only add if we found a meaningful sequence
-        if !new_indexes.is_empty() {
-            updated_highlight_index.insert(*uuid, new_indexes);
+        if !changed {
+            return flatten_blocks(blocks);
         }
+        blocks = rebuild_without_equalities(blocks, &remove);
     }
-
-    updated_highlight_index
 }
 
 // Calculates statistics for changes
@@ -250,8 +1054,13 @@ fn print_equal_char_ranges(char_diff_result: &CharDiffResult) {
     println!("\n🔢 Equal Char Pair Ranges:");
     for (i, pair) in char_diff_result.equal_matches.iter().enumerate() {
         println!(
-            "Pair {}: Old Index: {}, New Index: {}, Char: '{}'",
-            i, pair.old_info.index, pair.new_info.index, pair.old_info.char
+            "Pair {}: Old Index: {}, New Index: {}, Char: '{}', Old Bytes: {:?}, New Bytes: {:?}",
+            i,
+            pair.old_info.index,
+            pair.new_info.index,
+            pair.old_info.char,
+            pair.old_info.byte_range,
+            pair.new_info.byte_range
         );
     }
 }
@@ -289,11 +1098,282 @@ fn insert_bg() -> CustomColor {
     CustomColor::new(20, 70, 20) // Dark Forest Green Background
 }
 
+// Which of the three palette entries a rendered line falls into, decoupled
+// from `CharChangeType` because a whole *line* only ever needs one of these
+// three buckets, where a single char can also be "equal but shifted index".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LineKind {
+    Equal,
+    Delete,
+    Insert,
+}
+
+// Color palette for `render_unified`/`render_side_by_side`, decoupled from
+// printing so a caller can supply their own palette, or pass `None` for
+// `theme` at the call site to render plain uncolored text (e.g. to capture
+// output for a snapshot test, or for a terminal without truecolor support).
+#[derive(Debug, Clone, Copy)]
+struct Theme {
+    delete_fg: CustomColor,
+    delete_bg: CustomColor,
+    insert_fg: CustomColor,
+    insert_bg: CustomColor,
+    equal_fg: CustomColor,
+    equal_bg: CustomColor,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            delete_fg: delete_fg(),
+            delete_bg: delete_bg(),
+            insert_fg: insert_fg(),
+            insert_bg: insert_bg(),
+            equal_fg: color_equal(&CharChangeType::EqualIndex),
+            equal_bg: bg_color_equal(&CharChangeType::EqualIndex),
+        }
+    }
+}
+
+impl Theme {
+    fn colors(&self, kind: LineKind) -> (CustomColor, CustomColor) {
+        match kind {
+            LineKind::Equal => (self.equal_fg, self.equal_bg),
+            LineKind::Delete => (self.delete_fg, self.delete_bg),
+            LineKind::Insert => (self.insert_fg, self.insert_bg),
+        }
+    }
+}
+
+// Groups a char/grapheme-level `ChangeDetail` stream into line-level rows
+// for `render_unified`/`render_side_by_side`. A newline that is `Equal`
+// ends a line on both sides at once, the common case; a newline that is a
+// `Deletion`/`Insertion` ends a line on only the side it belongs to, so a
+// wholly added or removed line renders on its own instead of being glued
+// to whatever the other side happens to be mid-way through at that point
+// in the stream.
+fn group_into_rows(change_details: &[ChangeDetail]) -> Vec<DiffRow> {
+    let mut rows = Vec::new();
+    let mut old_line_no = 1usize;
+    let mut new_line_no = 1usize;
+    let mut old_buf = String::new();
+    let mut new_buf = String::new();
+    let mut old_started = false;
+    let mut new_started = false;
+    let mut changed = false;
+
+    for detail in change_details {
+        let is_newline = detail.value == "\n";
+        match detail.change_type {
+            CharChangeType::EqualIndex | CharChangeType::EqualDifferentIndex => {
+                old_started = true;
+                new_started = true;
+                if is_newline {
+                    rows.push(DiffRow {
+                        old_line_no: Some(old_line_no),
+                        old_text: Some(std::mem::take(&mut old_buf)),
+                        new_line_no: Some(new_line_no),
+                        new_text: Some(std::mem::take(&mut new_buf)),
+                        changed,
+                    });
+                    old_line_no += 1;
+                    new_line_no += 1;
+                    old_started = false;
+                    new_started = false;
+                    changed = false;
+                } else {
+                    old_buf.push_str(&detail.value);
+                    new_buf.push_str(&detail.value);
+                }
+            }
+            CharChangeType::Deletion => {
+                old_started = true;
+                changed = true;
+                if is_newline {
+                    rows.push(DiffRow {
+                        old_line_no: Some(old_line_no),
+                        old_text: Some(std::mem::take(&mut old_buf)),
+                        new_line_no: None,
+                        new_text: None,
+                        changed: true,
+                    });
+                    old_line_no += 1;
+                    old_started = false;
+                    changed = false;
+                } else {
+                    old_buf.push_str(&detail.value);
+                }
+            }
+            CharChangeType::Insertion => {
+                new_started = true;
+                changed = true;
+                if is_newline {
+                    rows.push(DiffRow {
+                        old_line_no: None,
+                        old_text: None,
+                        new_line_no: Some(new_line_no),
+                        new_text: Some(std::mem::take(&mut new_buf)),
+                        changed: true,
+                    });
+                    new_line_no += 1;
+                    new_started = false;
+                    changed = false;
+                } else {
+                    new_buf.push_str(&detail.value);
+                }
+            }
+        }
+    }
+
+    if old_started || new_started {
+        rows.push(DiffRow {
+            old_line_no: old_started.then_some(old_line_no),
+            old_text: old_started.then(|| std::mem::take(&mut old_buf)),
+            new_line_no: new_started.then_some(new_line_no),
+            new_text: new_started.then(|| std::mem::take(&mut new_buf)),
+            changed,
+        });
+    }
+
+    rows
+}
+
+fn fmt_line_no(line_no: Option<usize>) -> String {
+    line_no.map(|n| n.to_string()).unwrap_or_default()
+}
+
+// Writes `text` through `theme`'s palette for `kind`, or plain if `theme`
+// is `None`.
+fn write_themed(
+    out: &mut impl std::fmt::Write,
+    text: &str,
+    theme: Option<&Theme>,
+    kind: LineKind,
+) -> std::fmt::Result {
+    match theme {
+        Some(theme) => {
+            let (fg, bg) = theme.colors(kind);
+            write!(
+                out,
+                "{}",
+                text.truecolor(fg.r, fg.g, fg.b).on_truecolor(bg.r, bg.g, bg.b)
+            )
+        }
+        None => write!(out, "{text}"),
+    }
+}
+
+// Same as `write_themed`, then right-pads with plain (uncolored) spaces up
+// to `width` chars (as counted by `chars()`, not terminal display width) so
+// a side-by-side column stays aligned.
+fn write_themed_padded(
+    out: &mut impl std::fmt::Write,
+    text: &str,
+    width: usize,
+    theme: Option<&Theme>,
+    kind: LineKind,
+) -> std::fmt::Result {
+    write_themed(out, text, theme, kind)?;
+    for _ in 0..width.saturating_sub(text.chars().count()) {
+        out.write_char(' ')?;
+    }
+    Ok(())
+}
+
+// Renders `rows` (see `group_into_rows`) as a unified diff: a `+`/`-`/` `
+// gutter plus old/new line-number columns per line, in stream order. A row
+// with content on both sides that actually differs is shown as a deletion
+// immediately followed by its replacement, the same as `diff -u`. Writes
+// to any `fmt::Write` sink so output can be captured instead of printed.
+fn render_unified(
+    rows: &[DiffRow],
+    theme: Option<&Theme>,
+    out: &mut impl std::fmt::Write,
+) -> std::fmt::Result {
+    for row in rows {
+        match (&row.old_text, &row.new_text) {
+            (Some(old_text), Some(new_text)) if !row.changed => {
+                write!(
+                    out,
+                    "{:>4} {:>4}   ",
+                    fmt_line_no(row.old_line_no),
+                    fmt_line_no(row.new_line_no)
+                )?;
+                write_themed(out, old_text, theme, LineKind::Equal)?;
+                writeln!(out)?;
+            }
+            (Some(old_text), new_text) => {
+                write!(out, "{:>4} {:>4} - ", fmt_line_no(row.old_line_no), "")?;
+                write_themed(out, old_text, theme, LineKind::Delete)?;
+                writeln!(out)?;
+                if let Some(new_text) = new_text {
+                    write!(out, "{:>4} {:>4} + ", "", fmt_line_no(row.new_line_no))?;
+                    write_themed(out, new_text, theme, LineKind::Insert)?;
+                    writeln!(out)?;
+                }
+            }
+            (None, Some(new_text)) => {
+                write!(out, "{:>4} {:>4} + ", "", fmt_line_no(row.new_line_no))?;
+                write_themed(out, new_text, theme, LineKind::Insert)?;
+                writeln!(out)?;
+            }
+            (None, None) => {}
+        }
+    }
+    Ok(())
+}
+
+// Renders `rows` as two columns padded to the widest line seen on either
+// side, so old and new stay lined up visually as lines are added, removed,
+// or replaced further down. Writes to any `fmt::Write` sink so output can
+// be captured instead of printed.
+fn render_side_by_side(
+    rows: &[DiffRow],
+    theme: Option<&Theme>,
+    out: &mut impl std::fmt::Write,
+) -> std::fmt::Result {
+    let width = rows
+        .iter()
+        .flat_map(|row| [row.old_text.as_deref(), row.new_text.as_deref()])
+        .flatten()
+        .map(|text| text.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    for row in rows {
+        let marker = match (&row.old_text, &row.new_text) {
+            (Some(_), Some(_)) if !row.changed => ' ',
+            (Some(_), Some(_)) => '|',
+            (Some(_), None) => '<',
+            (None, Some(_)) => '>',
+            (None, None) => ' ',
+        };
+        let old_kind = if row.old_text.is_some() && !row.changed {
+            LineKind::Equal
+        } else {
+            LineKind::Delete
+        };
+        let new_kind = if row.new_text.is_some() && !row.changed {
+            LineKind::Equal
+        } else {
+            LineKind::Insert
+        };
+
+        write!(out, "{:>4} ", fmt_line_no(row.old_line_no))?;
+        write_themed_padded(out, row.old_text.as_deref().unwrap_or(""), width, theme, old_kind)?;
+        write!(out, " {marker} ")?;
+        write_themed_padded(out, row.new_text.as_deref().unwrap_or(""), width, theme, new_kind)?;
+        writeln!(out, " {:>4}", fmt_line_no(row.new_line_no))?;
+    }
+    Ok(())
+}
+
 // Definitions for required structs
 #[derive(Debug)]
 struct CharMetadata {
     index: usize,
     char: String,
+    byte_range: Range<usize>,
 }
 
 #[derive(Debug)]
@@ -325,3 +1405,126 @@ struct ChangeStats {
     insertions: usize,
     deletions: usize,
 }
+
+// One aligned row for `render_unified`/`render_side_by_side`, built by
+// `group_into_rows`. Either side is `None` when it has no line at this row
+// at all (a line purely added or purely removed); `changed` is true when
+// any deletion or insertion touched this row's line, so it is always true
+// for a one-sided row, and for a two-sided row it means the content differs.
+#[derive(Debug, Clone)]
+struct DiffRow {
+    old_line_no: Option<usize>,
+    old_text: Option<String>,
+    new_line_no: Option<usize>,
+    new_text: Option<String>,
+    changed: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An `old_offset` that lands mid-codepoint (as a drifted base or a
+    // foreign hand-crafted patch could produce) must surface as a structured
+    // `ContextMismatch`, not panic the `old[cursor..hunk.old_offset]` slice.
+    #[test]
+    fn apply_patch_rejects_non_char_boundary_offset_instead_of_panicking() {
+        let hunk = PatchHunk {
+            old_offset: 1, // 'é' is 2 bytes; byte 1 is mid-codepoint
+            context: String::new(),
+            deletion: "é".to_string(),
+            insertion: "e".to_string(),
+        };
+
+        let result = apply_patch("é", &[hunk]);
+
+        assert!(matches!(result, Err(PatchError::ContextMismatch { .. })));
+    }
+
+    // `serialize_patch` writes `context_len=`; `parse_patch` must check it
+    // against the context it actually reads rather than ignoring it.
+    #[test]
+    fn parse_patch_rejects_mismatched_context_len() {
+        let text = "@@ offset=0 context_len=99 @@\n=abc\n-x\n+y\n";
+
+        assert_eq!(parse_patch(text), Err(PatchError::MalformedPatch));
+    }
+
+    // A label whose first matched char is multi-byte must not advance the
+    // search cursor mid-codepoint, or the next `old[start..]` slice panics
+    // instead of just finding no further matches.
+    #[test]
+    fn build_highlight_index_does_not_panic_on_multibyte_label() {
+        let id = Uuid::new_v4();
+        let text = "say Éva loud";
+        let index = build_highlight_index(text, &[(id, "Éva")]);
+
+        let mention_start = text.find("Éva").unwrap();
+        let expected: Vec<usize> =
+            (mention_start..mention_start + "Éva".len()).collect();
+        assert_eq!(index.get(&id), Some(&expected));
+    }
+
+    // A multibyte char before the tracked mention shifts every following
+    // byte offset: `remap_offsets` must follow those shifts in bytes, not
+    // silently drop or mis-map them by reasoning in chars instead.
+    #[test]
+    fn remap_offsets_tracks_multibyte_prefix_in_bytes() {
+        let old = "é#AB";
+        let new = "éZZ#AB";
+        let config = DiffConfig::default();
+
+        let mention_start = old.find("#AB").unwrap();
+        let offsets: Vec<usize> = (mention_start..mention_start + "#AB".len()).collect();
+
+        let remapped = remap_offsets(old, new, &offsets, &config);
+
+        assert_eq!(remapped, vec![Some(4), Some(5), Some(6)]);
+    }
+
+    // An `Equal` unit's old and new byte ranges only coincide when nothing
+    // ahead of it on either side changed length; here a deleted leading
+    // char shifts the old-side range but not the new-side one, so the two
+    // must be tracked (and reported) separately.
+    #[test]
+    fn equal_unit_tracks_distinct_old_and_new_byte_ranges() {
+        let old = "xé#AB";
+        let new = "é#AB";
+        let config = DiffConfig::default();
+
+        let units = diff_units(old, new, &config);
+        let equal_e = units
+            .iter()
+            .find(|unit| unit.tag == ChangeTag::Equal && unit.text == "é")
+            .expect("the shared 'é' survives as an Equal unit");
+
+        assert_eq!(equal_e.old_byte_range, Some(1..3));
+        assert_eq!(equal_e.new_byte_range, Some(0..2));
+    }
+
+    // `build_offset_map`/`remap_offsets` must honor `config.unit`: with
+    // `DiffUnit::Grapheme`, the offset map is built from grapheme-cluster
+    // units rather than individual chars. "José" -> "Jose" replaces the
+    // whole `é` grapheme (2 bytes) with a plain `e`, so the surviving
+    // "#Jos" prefix maps byte-for-byte while both of `é`'s bytes have no
+    // image in `new` at all.
+    #[test]
+    fn remap_offsets_honors_grapheme_unit_config() {
+        let old = "#José team";
+        let new = "#Jose crew";
+        let config = DiffConfig {
+            unit: DiffUnit::Grapheme,
+            ..DiffConfig::default()
+        };
+
+        let mention_start = old.find("#José").unwrap();
+        let offsets: Vec<usize> = (mention_start..mention_start + "#José".len()).collect();
+
+        let remapped = remap_offsets(old, new, &offsets, &config);
+
+        assert_eq!(
+            remapped,
+            vec![Some(0), Some(1), Some(2), Some(3), None, None]
+        );
+    }
+}